@@ -1,6 +1,9 @@
 use anyhow::{Context, Result};
+use log::{error, info, warn, LevelFilter};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
+use std::time::Duration;
 
 #[derive(Debug, Deserialize)]
 struct Config {
@@ -12,6 +15,34 @@ struct Config {
 struct CloudflareConfig {
     api_token: String,
     zone_id: String,
+    #[serde(default = "default_interval_secs")]
+    interval_secs: u64,
+    /// Ordered list of IPv4 echo endpoints, tried until one succeeds.
+    #[serde(default = "default_ipv4_providers")]
+    ipv4_providers: Vec<String>,
+    /// Ordered list of IPv6 echo endpoints, tried until one succeeds.
+    #[serde(default = "default_ipv6_providers")]
+    ipv6_providers: Vec<String>,
+}
+
+fn default_interval_secs() -> u64 {
+    300
+}
+
+fn default_ipv4_providers() -> Vec<String> {
+    vec![
+        "https://ipinfo.io/ip".to_string(),
+        "https://api.ipify.org".to_string(),
+        "https://ifconfig.me/ip".to_string(),
+    ]
+}
+
+fn default_ipv6_providers() -> Vec<String> {
+    vec![
+        "https://api6.ipify.org".to_string(),
+        "https://ipv6.icanhazip.com".to_string(),
+        "https://ifconfig.me/ip".to_string(),
+    ]
 }
 
 #[derive(Debug, Deserialize)]
@@ -19,6 +50,26 @@ struct RecordConfig {
     name: String,
     #[serde(rename = "type")]
     record_type: String,
+    #[serde(default)]
+    source: IpSource,
+    /// Name of the local interface to read from when `source: interface`.
+    interface: Option<String>,
+    /// TTL in seconds; omit for Cloudflare's automatic TTL.
+    ttl: Option<u32>,
+    /// Whether the record is proxied through Cloudflare; omit to preserve the
+    /// record's existing proxy setting.
+    proxied: Option<bool>,
+}
+
+/// Where a record's content IP is obtained from.
+#[derive(Debug, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum IpSource {
+    /// Query a public IP echo service over HTTP (the default).
+    #[default]
+    Public,
+    /// Read the address directly off a local network interface via netlink.
+    Interface,
 }
 
 #[derive(Debug, Deserialize)]
@@ -52,6 +103,24 @@ struct ApiError {
     message: String,
 }
 
+/// Log each Cloudflare API error at error level (with the record name and the
+/// `code`/`message` so it is greppable in `journalctl`) and build the error to
+/// propagate up the stack.
+fn api_failure(name: &str, errors: Option<Vec<ApiError>>) -> anyhow::Error {
+    match errors {
+        Some(errors) => {
+            for err in &errors {
+                error!(
+                    "Cloudflare API error for {}: code={} message={}",
+                    name, err.code, err.message
+                );
+            }
+            anyhow::anyhow!("Cloudflare API request for {} failed: {:?}", name, errors)
+        }
+        None => anyhow::anyhow!("Cloudflare API request for {} failed with no error detail", name),
+    }
+}
+
 // Remove the IpResponse struct since we're getting plain text
 // We'll use a simple string for IP responses
 
@@ -88,15 +157,20 @@ impl CloudflareClient {
         let cf_response: CloudflareResponse = response.json().await?;
 
         if !cf_response.success {
-            if let Some(errors) = cf_response.errors {
-                anyhow::bail!("API errors: {:?}", errors);
-            }
+            return Err(api_failure(name, cf_response.errors));
         }
 
         Ok(cf_response.result.unwrap_or_default())
     }
 
-    async fn create_dns_record(&self, name: &str, record_type: &str, content: &str) -> Result<DnsRecord> {
+    async fn create_dns_record(
+        &self,
+        name: &str,
+        record_type: &str,
+        content: &str,
+        ttl: Option<u32>,
+        proxied: Option<bool>,
+    ) -> Result<DnsRecord> {
         let url = format!(
             "https://api.cloudflare.com/client/v4/zones/{}/dns_records",
             self.zone_id
@@ -107,8 +181,8 @@ impl CloudflareClient {
             name: name.to_string(),
             record_type: record_type.to_string(),
             content: content.to_string(),
-            ttl: Some(1), // Auto TTL
-            proxied: Some(false),
+            ttl: Some(ttl.unwrap_or(1)), // Default to auto TTL
+            proxied: Some(proxied.unwrap_or(false)),
         };
 
         let response = self
@@ -123,15 +197,21 @@ impl CloudflareClient {
         let cf_response: SingleRecordResponse = response.json().await?;
 
         if !cf_response.success {
-            if let Some(errors) = cf_response.errors {
-                anyhow::bail!("API errors: {:?}", errors);
-            }
+            return Err(api_failure(name, cf_response.errors));
         }
 
         cf_response.result.context("No result returned")
     }
 
-    async fn update_dns_record(&self, record_id: &str, name: &str, record_type: &str, content: &str) -> Result<DnsRecord> {
+    async fn update_dns_record(
+        &self,
+        record_id: &str,
+        name: &str,
+        record_type: &str,
+        content: &str,
+        ttl: Option<u32>,
+        proxied: Option<bool>,
+    ) -> Result<DnsRecord> {
         let url = format!(
             "https://api.cloudflare.com/client/v4/zones/{}/dns_records/{}",
             self.zone_id, record_id
@@ -142,8 +222,8 @@ impl CloudflareClient {
             name: name.to_string(),
             record_type: record_type.to_string(),
             content: content.to_string(),
-            ttl: Some(1),
-            proxied: Some(false),
+            ttl: Some(ttl.unwrap_or(1)),
+            proxied,
         };
 
         let response = self
@@ -158,90 +238,427 @@ impl CloudflareClient {
         let cf_response: SingleRecordResponse = response.json().await?;
 
         if !cf_response.success {
-            if let Some(errors) = cf_response.errors {
-                anyhow::bail!("API errors: {:?}", errors);
-            }
+            return Err(api_failure(name, cf_response.errors));
         }
 
         cf_response.result.context("No result returned")
     }
+
+    /// Reconcile a single record against the given public IP, creating or
+    /// updating it in Cloudflare only when the content actually differs.
+    async fn check(&self, record_config: &RecordConfig, current_ip: &str) -> Result<()> {
+        let existing_records = self
+            .get_dns_records(&record_config.name, &record_config.record_type)
+            .await?;
+
+        if let Some(existing) = existing_records.first() {
+            // Record exists - check if update needed
+            if existing.content != current_ip {
+                warn!(
+                    "{}: IP mismatch, updating record from {} to {}",
+                    record_config.name, existing.content, current_ip
+                );
+                // Preserve the record's existing proxy setting unless the
+                // config explicitly overrides it, so we never silently flip
+                // Cloudflare proxying off on records we manage.
+                let proxied = record_config.proxied.or(existing.proxied);
+                self.update_dns_record(
+                    existing.id.as_ref().context("DNS record missing id")?,
+                    &record_config.name,
+                    &record_config.record_type,
+                    current_ip,
+                    record_config.ttl,
+                    proxied,
+                )
+                .await?;
+                info!("{}: record updated successfully", record_config.name);
+            } else {
+                info!("{}: record already up to date", record_config.name);
+            }
+        } else {
+            // Record doesn't exist - create it
+            info!("{}: record not found, creating", record_config.name);
+            self.create_dns_record(
+                &record_config.name,
+                &record_config.record_type,
+                current_ip,
+                record_config.ttl,
+                record_config.proxied,
+            )
+            .await?;
+            info!("{}: record created successfully", record_config.name);
+        }
+
+        Ok(())
+    }
 }
 
-async fn get_public_ip(ip_type: &str) -> Result<String> {
-    let client = reqwest::Client::new();
-    
-    let url = match ip_type {
-        "A" => "https://ipinfo.io/ip",
-        "AAAA" => "https://ifconfig.me/ip",
+async fn get_public_ip(ip_type: &str, providers: &[String]) -> Result<String> {
+    let want_v6 = match ip_type {
+        "A" => false,
+        "AAAA" => true,
         _ => anyhow::bail!("Unsupported record type: {}", ip_type),
     };
 
-    let response = client.get(url).send().await?;
-    let ip = response.text().await?;
-    
-    // Remove any whitespace or newlines
-    let ip = ip.trim().to_string();
-    
-    // Validate IP format
-    if ip.is_empty() {
-        anyhow::bail!("Empty IP response");
+    let client = reqwest::Client::new();
+
+    // Try each provider in order, accepting the first one that returns a
+    // well-formed address of the expected family.
+    let mut last_error = None;
+    for url in providers {
+        match fetch_ip(&client, url, want_v6).await {
+            Ok(ip) => return Ok(ip.to_string()),
+            Err(err) => {
+                warn!("Provider {} failed: {:#}", url, err);
+                last_error = Some(err);
+            }
+        }
+    }
+
+    match last_error {
+        Some(err) => Err(err.context("all IP providers failed")),
+        None => anyhow::bail!("no IP providers configured for {} records", ip_type),
+    }
+}
+
+/// Fetch and validate a single provider's response, rejecting error statuses,
+/// unparseable bodies (e.g. HTML error pages), and the wrong address family.
+async fn fetch_ip(client: &reqwest::Client, url: &str, want_v6: bool) -> Result<std::net::IpAddr> {
+    let body = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    parse_ip_response(&body, want_v6)
+}
+
+/// Parse a provider's response body into an `IpAddr`, rejecting unparseable
+/// bodies (e.g. HTML error pages) and addresses of the wrong family.
+fn parse_ip_response(body: &str, want_v6: bool) -> Result<std::net::IpAddr> {
+    use std::net::IpAddr;
+
+    let trimmed = body.trim();
+    let ip: IpAddr = trimmed
+        .parse()
+        .with_context(|| format!("invalid IP response: {:?}", trimmed))?;
+
+    match (ip, want_v6) {
+        (IpAddr::V6(_), false) => anyhow::bail!("got IPv6 address for an A record: {}", ip),
+        (IpAddr::V4(_), true) => anyhow::bail!("got IPv4 address for an AAAA record: {}", ip),
+        _ => Ok(ip),
+    }
+}
+
+/// Read the first global-scope address of the requested family off a local
+/// interface using a netlink route socket.
+///
+/// This avoids depending on a third-party IP echo service and is the only way
+/// to learn an address that lives on the local NIC (e.g. an IPv6 delegated
+/// directly to the host), which never traverses a public echo endpoint.
+async fn get_interface_ip(interface: &str, record_type: &str) -> Result<String> {
+    use futures::stream::TryStreamExt;
+    use netlink_packet_route::address::{AddressAttribute, AddressFlag, AddressScope};
+    use netlink_packet_route::AddressFamily;
+
+    let family = match record_type {
+        "A" => AddressFamily::Inet,
+        "AAAA" => AddressFamily::Inet6,
+        _ => anyhow::bail!("Unsupported record type: {}", record_type),
+    };
+
+    let (connection, handle, _) = rtnetlink::new_connection()
+        .context("Failed to open netlink route socket")?;
+    tokio::spawn(connection);
+
+    // Resolve the interface name to its kernel index.
+    let link = handle
+        .link()
+        .get()
+        .match_name(interface.to_string())
+        .execute()
+        .try_next()
+        .await?
+        .with_context(|| format!("Interface {} not found", interface))?;
+    let index = link.header.index;
+
+    let mut addresses = handle
+        .address()
+        .get()
+        .set_link_index_filter(index)
+        .execute();
+
+    while let Some(msg) = addresses.try_next().await? {
+        if msg.header.family != family {
+            continue;
+        }
+        // Only consider global-scope addresses; skip link-local and host scopes.
+        if msg.header.scope != AddressScope::Universe {
+            continue;
+        }
+        // Skip IPv6 temporary/privacy addresses, preferring the stable one.
+        // On Linux IFA_F_TEMPORARY shares its bit with IFA_F_SECONDARY, so the
+        // crate surfaces both as `AddressFlag::Secondary`.
+        if msg.attributes.iter().any(|attr| {
+            matches!(attr, AddressAttribute::Flags(flags) if flags.contains(&AddressFlag::Secondary))
+        }) {
+            continue;
+        }
+
+        if let Some(addr) = msg.attributes.iter().find_map(|attr| match attr {
+            AddressAttribute::Address(addr) => Some(*addr),
+            _ => None,
+        }) {
+            // A universe scope alone does not mean globally routable: the kernel
+            // marks RFC1918 / ULA private ranges as universe scope too. Reject
+            // link-local and private addresses so a NATed host never publishes a
+            // private address to public DNS.
+            if !is_global_unicast(addr) {
+                continue;
+            }
+            return Ok(addr.to_string());
+        }
     }
-    
-    Ok(ip)
+
+    anyhow::bail!(
+        "No global-scope {} address found on interface {}",
+        record_type,
+        interface
+    )
+}
+
+/// Whether an address is a globally-routable unicast address safe to publish
+/// to public DNS, rejecting link-local and RFC1918 / ULA private ranges.
+fn is_global_unicast(addr: std::net::IpAddr) -> bool {
+    use std::net::IpAddr;
+    match addr {
+        IpAddr::V4(v4) => {
+            !(v4.is_private() || v4.is_link_local() || v4.is_loopback() || v4.is_unspecified())
+        }
+        IpAddr::V6(v6) => {
+            let is_link_local = (v6.segments()[0] & 0xffc0) == 0xfe80;
+            // Unique-local addresses live in fc00::/7.
+            let is_unique_local = (v6.segments()[0] & 0xfe00) == 0xfc00;
+            !(is_link_local || is_unique_local || v6.is_loopback() || v6.is_unspecified())
+        }
+    }
+}
+
+/// Run a single reconciliation pass over every configured record.
+///
+/// `last_ips` caches the last public IP seen per record so that repeated
+/// polls in daemon mode can skip the Cloudflare round-trip entirely when the
+/// address has not changed.
+///
+/// Every record is attempted; an error is returned after the pass if any of
+/// them failed.
+async fn run_once(
+    client: &CloudflareClient,
+    config: &CloudflareConfig,
+    records: &[RecordConfig],
+    last_ips: &mut HashMap<String, String>,
+) -> Result<()> {
+    let mut failed = 0;
+    for record_config in records {
+        // Isolate each record: a transient failure on one (a provider blip, a
+        // 5xx) must not skip the records that follow it in this tick.
+        if let Err(err) = reconcile_record(client, config, record_config, last_ips).await {
+            error!("{}: reconciliation failed: {:#}", record_config.name, err);
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{} of {} records failed to reconcile", failed, records.len());
+    }
+
+    Ok(())
+}
+
+/// Reconcile a single configured record: resolve its current IP, skip the
+/// Cloudflare round-trip when it matches the cached value, and otherwise apply
+/// the change through `CloudflareClient::check`.
+async fn reconcile_record(
+    client: &CloudflareClient,
+    config: &CloudflareConfig,
+    record_config: &RecordConfig,
+    last_ips: &mut HashMap<String, String>,
+) -> Result<()> {
+    info!(
+        "Processing {} record for {}",
+        record_config.record_type, record_config.name
+    );
+
+    // Get the current IP from the record's configured source.
+    let current_ip = match record_config.source {
+        IpSource::Public => {
+            let providers = match record_config.record_type.as_str() {
+                "AAAA" => &config.ipv6_providers,
+                _ => &config.ipv4_providers,
+            };
+            get_public_ip(&record_config.record_type, providers).await?
+        }
+        IpSource::Interface => {
+            let interface = record_config
+                .interface
+                .as_deref()
+                .context("source: interface requires an `interface` field")?;
+            get_interface_ip(interface, &record_config.record_type).await?
+        }
+    };
+    info!("Current IP ({}): {}", record_config.record_type, current_ip);
+
+    // Skip the Cloudflare round-trip when the IP matches our cached value.
+    let cache_key = format!("{}:{}", record_config.record_type, record_config.name);
+    if last_ips.get(&cache_key) == Some(&current_ip) {
+        info!("{}: IP already set, skipping", record_config.name);
+        return Ok(());
+    }
+
+    client.check(record_config, &current_ip).await?;
+    last_ips.insert(cache_key, current_ip);
+
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let daemon = args.iter().any(|arg| arg == "--daemon");
+
+    init_logging(log_level_from_args(&args));
+
     // Load configuration
     let config_content = fs::read_to_string("config.yml")
         .context("Failed to read config.yml")?;
     let config: Config = serde_yaml::from_str(&config_content)
         .context("Failed to parse config.yml")?;
 
+    let interval = Duration::from_secs(config.cloudflare.interval_secs);
     let client = CloudflareClient::new(
-        config.cloudflare.api_token,
-        config.cloudflare.zone_id,
+        config.cloudflare.api_token.clone(),
+        config.cloudflare.zone_id.clone(),
     );
 
-    // Process each record
-    for record_config in config.records {
-        println!("\nProcessing {} record for {}", record_config.record_type, record_config.name);
+    let mut last_ips: HashMap<String, String> = HashMap::new();
 
-        // Get current public IP
-        let current_ip = get_public_ip(&record_config.record_type).await?;
-        println!("Current public IP ({}): {}", record_config.record_type, current_ip);
+    if daemon {
+        info!(
+            "Starting in daemon mode, polling every {}s",
+            config.cloudflare.interval_secs
+        );
+        loop {
+            // A single failed poll (network blip, 5xx from Cloudflare) must not
+            // take down the daemon; log it and try again next tick.
+            if let Err(err) =
+                run_once(&client, &config.cloudflare, &config.records, &mut last_ips).await
+            {
+                error!("Poll failed, retrying next interval: {:#}", err);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    } else {
+        run_once(&client, &config.cloudflare, &config.records, &mut last_ips).await?;
+        info!("All records processed successfully");
+        Ok(())
+    }
+}
 
-        // Get existing DNS records
-        let existing_records = client
-            .get_dns_records(&record_config.name, &record_config.record_type)
-            .await?;
+/// Resolve the desired log level from a `--log-level <level>` flag, falling
+/// back to the `CLOUDFLARE_DDNS_LOG` environment variable and finally `info`.
+fn log_level_from_args(args: &[String]) -> LevelFilter {
+    let raw = args
+        .iter()
+        .position(|arg| arg == "--log-level")
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+        .or_else(|| std::env::var("CLOUDFLARE_DDNS_LOG").ok());
+
+    match raw.as_deref().map(str::to_ascii_lowercase).as_deref() {
+        Some("error") => LevelFilter::Error,
+        Some("warn") => LevelFilter::Warn,
+        Some("debug") => LevelFilter::Debug,
+        Some("trace") => LevelFilter::Trace,
+        _ => LevelFilter::Info,
+    }
+}
 
-        if let Some(existing) = existing_records.first() {
-            // Record exists - check if update needed
-            if existing.content != current_ip {
-                println!("IP mismatch! Updating record from {} to {}", existing.content, current_ip);
-                client
-                    .update_dns_record(
-                        existing.id.as_ref().unwrap(),
-                        &record_config.name,
-                        &record_config.record_type,
-                        &current_ip,
-                    )
-                    .await?;
-                println!("✓ Record updated successfully");
-            } else {
-                println!("✓ Record already up to date");
+/// Initialise the logging backend. When launched under systemd (detected via
+/// the journal stream), route records to the journal with proper severity;
+/// otherwise fall back to a plain stderr logger.
+fn init_logging(level: LevelFilter) {
+    if systemd_journal_logger::connected_to_journal() {
+        let installed = systemd_journal_logger::JournalLog::new()
+            .map_err(|err| err.to_string())
+            .and_then(|logger| logger.install().map_err(|err| err.to_string()));
+        match installed {
+            Ok(()) => {
+                log::set_max_level(level);
+                return;
+            }
+            Err(err) => {
+                // Journal setup failed even though we appear to run under
+                // systemd; fall back to stderr rather than dropping every line.
+                eprintln!("Failed to initialise journal logger, falling back to stderr: {err}");
             }
-        } else {
-            // Record doesn't exist - create it
-            println!("Record not found. Creating new record...");
-            client
-                .create_dns_record(&record_config.name, &record_config.record_type, &current_ip)
-                .await?;
-            println!("✓ Record created successfully");
         }
     }
 
-    println!("\n✓ All records processed successfully!");
-    Ok(())
+    env_logger::Builder::new().filter_level(level).init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::IpAddr;
+
+    #[test]
+    fn parse_ip_response_accepts_matching_family() {
+        assert_eq!(
+            parse_ip_response("203.0.113.5\n", false).unwrap(),
+            "203.0.113.5".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            parse_ip_response(" 2001:db8::1 ", true).unwrap(),
+            "2001:db8::1".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_ip_response_rejects_wrong_family() {
+        // IPv6 returned for an A record, and IPv4 for an AAAA record.
+        assert!(parse_ip_response("2001:db8::1", false).is_err());
+        assert!(parse_ip_response("203.0.113.5", true).is_err());
+    }
+
+    #[test]
+    fn parse_ip_response_rejects_malformed_body() {
+        assert!(parse_ip_response("<html>502 Bad Gateway</html>", false).is_err());
+        assert!(parse_ip_response("not an ip", false).is_err());
+        assert!(parse_ip_response("", false).is_err());
+    }
+
+    #[test]
+    fn log_level_from_args_flag_precedence_and_fallback() {
+        // Kept in one test because it mutates the shared process environment.
+        std::env::set_var("CLOUDFLARE_DDNS_LOG", "error");
+
+        // The flag takes precedence over the environment variable...
+        let args = vec!["ddns".to_string(), "--log-level".to_string(), "WARN".to_string()];
+        assert_eq!(log_level_from_args(&args), LevelFilter::Warn);
+
+        // ...and flag parsing is case-insensitive.
+        let args = vec!["ddns".to_string(), "--log-level".to_string(), "Debug".to_string()];
+        assert_eq!(log_level_from_args(&args), LevelFilter::Debug);
+
+        // With no flag, the environment variable is honoured.
+        let args = vec!["ddns".to_string()];
+        assert_eq!(log_level_from_args(&args), LevelFilter::Error);
+
+        // With neither, we fall back to info.
+        std::env::remove_var("CLOUDFLARE_DDNS_LOG");
+        assert_eq!(log_level_from_args(&args), LevelFilter::Info);
+    }
 }
\ No newline at end of file